@@ -1,19 +1,20 @@
 #[cfg(test)]
 mod tests;
+mod testsuite;
 
 use anyhow::{Context, Result, anyhow};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use colored::*;
 use nom::{
     IResult,
     branch::alt,
     bytes::complete::{tag, take_while1},
     character::complete::{char, multispace0, multispace1},
-    combinator::{map, recognize, rest},
+    combinator::{cut, map, recognize, rest},
     multi::separated_list1,
-    sequence::{delimited, preceded, terminated, tuple},
+    sequence::{delimited, preceded, tuple},
 };
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::{self, File};
 use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
@@ -24,16 +25,40 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 enum ProcessorError {
     #[error("Mismatched #endif found at line {line_num} in {path}")]
-    MismatchedEndif { line_num: usize, path: PathBuf },
-    #[error("Mismatched #if without corresponding #endif at end of file {path}")]
-    MismatchedIf { path: PathBuf },
+    MismatchedEndif {
+        line_num: usize,
+        col_start: usize,
+        col_end: usize,
+        path: PathBuf,
+    },
+    #[error("Mismatched #if without corresponding #endif, opened at line {line_num} in {path}")]
+    MismatchedIf {
+        line_num: usize,
+        col_start: usize,
+        col_end: usize,
+        path: PathBuf,
+    },
+    #[error("Mismatched #elif/#else found at line {line_num} in {path}: {reason}")]
+    MismatchedElse {
+        line_num: usize,
+        path: PathBuf,
+        reason: String,
+    },
     #[error("Failed to parse condition '{condition}' at line {line_num} in {path}: {reason}")]
     ConditionParse {
         condition: String,
         line_num: usize,
+        col_start: usize,
+        col_end: usize,
         path: PathBuf,
         reason: String,
     },
+    #[error("Unknown variable '{name}' referenced at line {line_num} in {path}")]
+    UnknownVar {
+        line_num: usize,
+        name: String,
+        path: PathBuf,
+    },
     #[error("I/O error processing file {path}: {source}")]
     Io {
         path: PathBuf,
@@ -42,39 +67,100 @@ enum ProcessorError {
     },
 }
 
+impl ProcessorError {
+    /// Name of the enum variant, used by the fixture testsuite to match `#!ERROR` annotations.
+    pub(crate) fn variant_name(&self) -> &'static str {
+        match self {
+            ProcessorError::MismatchedEndif { .. } => "MismatchedEndif",
+            ProcessorError::MismatchedIf { .. } => "MismatchedIf",
+            ProcessorError::MismatchedElse { .. } => "MismatchedElse",
+            ProcessorError::ConditionParse { .. } => "ConditionParse",
+            ProcessorError::UnknownVar { .. } => "UnknownVar",
+            ProcessorError::Io { .. } => "Io",
+        }
+    }
+
+    /// Line number the error occurred at, if the variant tracks one.
+    pub(crate) fn line_num(&self) -> Option<usize> {
+        match self {
+            ProcessorError::MismatchedEndif { line_num, .. }
+            | ProcessorError::MismatchedIf { line_num, .. }
+            | ProcessorError::MismatchedElse { line_num, .. }
+            | ProcessorError::ConditionParse { line_num, .. }
+            | ProcessorError::UnknownVar { line_num, .. } => Some(*line_num),
+            ProcessorError::Io { .. } => None,
+        }
+    }
+
+    /// Byte span `(col_start, col_end)` of the offending text within its line, if tracked.
+    pub(crate) fn span(&self) -> Option<(usize, usize)> {
+        match self {
+            ProcessorError::MismatchedEndif {
+                col_start, col_end, ..
+            }
+            | ProcessorError::MismatchedIf {
+                col_start, col_end, ..
+            }
+            | ProcessorError::ConditionParse {
+                col_start, col_end, ..
+            } => Some((*col_start, *col_end)),
+            ProcessorError::MismatchedElse { .. }
+            | ProcessorError::UnknownVar { .. }
+            | ProcessorError::Io { .. } => None,
+        }
+    }
+}
+
 // --- Data Structures for Parsed Conditions ---
 #[derive(Debug, PartialEq, Clone)]
 enum Condition {
-    Single(String),
-    And(Vec<String>),
-    Or(Vec<String>),
+    Flag(String),
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
 }
 
 impl Condition {
     /// Evaluates the parsed condition against the provided flags.
-    /// Also collects all flags encountered in the condition into `used_flags`.
+    /// Also collects all flags encountered along the evaluated path into `used_flags`.
+    /// `And`/`Or` visit every operand (no short-circuiting of the visit itself), so a
+    /// flag that turns out not to matter for the boolean result is still marked used.
     fn evaluate(&self, flags: &HashSet<String>, used_flags: &mut HashSet<String>) -> bool {
         match self {
-            Condition::Single(flag) => {
+            Condition::Flag(flag) => {
                 used_flags.insert(flag.clone());
                 flags.contains(flag)
             }
+            Condition::Not(inner) => !inner.evaluate(flags, used_flags),
             Condition::And(terms) => {
-                used_flags.extend(terms.iter().cloned());
-                terms.iter().all(|term| flags.contains(term))
+                let mut all_true = true;
+                for term in terms {
+                    if !term.evaluate(flags, used_flags) {
+                        all_true = false;
+                    }
+                }
+                all_true
             }
             Condition::Or(terms) => {
-                used_flags.extend(terms.iter().cloned());
-                terms.iter().any(|term| flags.contains(term))
+                let mut any_true = false;
+                for term in terms {
+                    if term.evaluate(flags, used_flags) {
+                        any_true = true;
+                    }
+                }
+                any_true
             }
         }
     }
 
-    /// Extracts all flag names mentioned in the condition.
+    /// Extracts all flag names mentioned anywhere in the condition tree.
     fn mentioned_flags(&self) -> Vec<String> {
         match self {
-            Condition::Single(flag) => vec![flag.clone()],
-            Condition::And(terms) | Condition::Or(terms) => terms.clone(),
+            Condition::Flag(flag) => vec![flag.clone()],
+            Condition::Not(inner) => inner.mentioned_flags(),
+            Condition::And(terms) | Condition::Or(terms) => {
+                terms.iter().flat_map(Condition::mentioned_flags).collect()
+            }
         }
     }
 }
@@ -83,89 +169,185 @@ impl Condition {
 mod parser {
     use super::*; // Import necessary items from outer scope
 
-    // Represents the outcome of parsing a single line
+    // Represents the outcome of parsing a single line. `If`/`Elif`/`Endif` carry the byte span
+    // `(col_start, col_end)` of their condition (or the bare `#endif` keyword) within the line,
+    // for compiler-style caret diagnostics.
     #[derive(Debug, PartialEq)]
     pub(super) enum LineParseResult<'a> {
-        If(Condition),
-        Endif,
+        If(Condition, (usize, usize)),
+        Elif(Condition, (usize, usize)),
+        Else,
+        Endif(usize, usize),
         Content(&'a str), // The actual content line
     }
 
-    // Basic identifier/flag parser (non-whitespace, non-parenthesis)
+    // Basic identifier parser, shared by flag names and `${...}` variable names
+    // (non-whitespace, non-parenthesis, non-brace).
     fn identifier(input: &str) -> IResult<&str, &str> {
-        take_while1(|c: char| !c.is_whitespace() && c != '(' && c != ')')(input)
+        take_while1(|c: char| {
+            !c.is_whitespace() && c != '(' && c != ')' && c != '{' && c != '}'
+        })(input)
+    }
+
+    // Parser for a single `${identifier}` variable placeholder, returning the variable name.
+    pub(super) fn parse_var_token(input: &str) -> IResult<&str, &str> {
+        delimited(tag("${"), identifier, char('}'))(input)
     }
 
-    // Parser for "(and flag1 flag2 ...)"
+    // Parser for "(and cond1 cond2 ...)", where each operand may itself be a
+    // bare flag or a nested parenthesized condition.
     fn parse_and(input: &str) -> IResult<&str, Condition> {
         map(
             delimited(
                 tag("(and"),
-                preceded(multispace1, separated_list1(multispace1, identifier)),
+                preceded(
+                    multispace1,
+                    separated_list1(multispace1, parse_condition_type),
+                ),
                 preceded(multispace0, char(')')),
             ),
-            |flags: Vec<&str>| Condition::And(flags.into_iter().map(String::from).collect()),
+            Condition::And,
         )(input)
     }
 
-    // Parser for "(or flag1 flag2 ...)"
+    // Parser for "(or cond1 cond2 ...)", where each operand may itself be a
+    // bare flag or a nested parenthesized condition.
     fn parse_or(input: &str) -> IResult<&str, Condition> {
         map(
             delimited(
                 tag("(or"),
-                preceded(multispace1, separated_list1(multispace1, identifier)),
+                preceded(
+                    multispace1,
+                    separated_list1(multispace1, parse_condition_type),
+                ),
+                preceded(multispace0, char(')')),
+            ),
+            Condition::Or,
+        )(input)
+    }
+
+    // Parser for "(not cond)", requiring exactly one operand.
+    fn parse_not(input: &str) -> IResult<&str, Condition> {
+        map(
+            delimited(
+                tag("(not"),
+                preceded(multispace1, parse_condition_type),
                 preceded(multispace0, char(')')),
             ),
-            |flags: Vec<&str>| Condition::Or(flags.into_iter().map(String::from).collect()),
+            |inner| Condition::Not(Box::new(inner)),
         )(input)
     }
 
-    // Parser for a single flag condition
-    fn parse_single(input: &str) -> IResult<&str, Condition> {
-        map(identifier, |flag| Condition::Single(flag.to_string()))(input)
+    // Parser for a bare flag condition
+    fn parse_flag(input: &str) -> IResult<&str, Condition> {
+        map(identifier, |flag| Condition::Flag(flag.to_string()))(input)
     }
 
-    // Parser for any valid condition
-    fn parse_condition(input: &str) -> IResult<&str, Condition> {
-        alt((parse_and, parse_or, parse_single))(input)
+    // Recursive-descent entry point for any condition: a parenthesized
+    // `not`/`and`/`or` expression (whose operands are themselves conditions),
+    // or a bare flag identifier.
+    pub(super) fn parse_condition_type(input: &str) -> IResult<&str, Condition> {
+        alt((parse_not, parse_and, parse_or, parse_flag))(input)
     }
 
-    // Parser for "#if condition" line
+    // Parser for "#if condition" line. Tracks the byte span of the condition substring
+    // within `input` (the consumed length nom reports via the remaining input) so callers
+    // can underline it in diagnostics.
+    //
+    // Only the leading `#if` tag is allowed to fail recoverably (so `alt` can try the other
+    // directives, or fall back to `Content`, for lines that aren't `#if` at all). Everything
+    // after that — the required separating space and the condition itself — is wrapped in
+    // `cut` so a malformed `#if` line becomes `Err::Failure` instead of silently falling
+    // through to `Content` as plain text.
     fn parse_if_directive(input: &str) -> IResult<&str, LineParseResult> {
+        let (after_tag, _) = tuple((multispace0, tag("#if")))(input)?;
+        let (after_prefix, _) = cut(multispace1)(after_tag)?;
+        let col_start = input.len() - after_prefix.len();
+        let (remaining, condition) = cut(parse_condition_type)(after_prefix)?;
+        let col_end = col_start + (after_prefix.len() - remaining.len());
+        let (remaining, _) = multispace0(remaining)?;
+        Ok((remaining, LineParseResult::If(condition, (col_start, col_end))))
+    }
+
+    // Parser for "#elif condition" line. See `parse_if_directive` for the span convention
+    // and why everything past the tag is parsed with `cut`.
+    fn parse_elif_directive(input: &str) -> IResult<&str, LineParseResult> {
+        let (after_tag, _) = tuple((multispace0, tag("#elif")))(input)?;
+        let (after_prefix, _) = cut(multispace1)(after_tag)?;
+        let col_start = input.len() - after_prefix.len();
+        let (remaining, condition) = cut(parse_condition_type)(after_prefix)?;
+        let col_end = col_start + (after_prefix.len() - remaining.len());
+        let (remaining, _) = multispace0(remaining)?;
+        Ok((remaining, LineParseResult::Elif(condition, (col_start, col_end))))
+    }
+
+    // Parser for "#else" line
+    fn parse_else_directive(input: &str) -> IResult<&str, LineParseResult> {
         map(
-            preceded(
-                tuple((multispace0, tag("#if"), multispace1)),
-                // Important: consume trailing whitespace/newline after condition
-                terminated(parse_condition, multispace0),
-            ),
-            LineParseResult::If,
+            recognize(tuple((multispace0, tag("#else"), multispace0))),
+            |_| LineParseResult::Else,
         )(input)
     }
 
-    // Parser for "#endif" line
+    // Parser for "#endif" line. Tracks the span of the `#endif` keyword itself, since a
+    // mismatched endif has no condition to underline.
     fn parse_endif_directive(input: &str) -> IResult<&str, LineParseResult> {
-        map(
-            // Ensure the whole line is matched (or just whitespace after #endif)
-            recognize(tuple((multispace0, tag("#endif"), multispace0))),
-            |_| LineParseResult::Endif,
-        )(input)
+        let (after_ws, _) = multispace0(input)?;
+        let col_start = input.len() - after_ws.len();
+        let (remaining, _) = tag("#endif")(after_ws)?;
+        let col_end = col_start + "#endif".len();
+        let (remaining, _) = multispace0(remaining)?;
+        Ok((remaining, LineParseResult::Endif(col_start, col_end)))
     }
 
     // Top-level line parser
-    // Tries to parse #if, then #endif. If both fail, it's content.
+    // Tries #if, #elif, #else, then #endif. If all fail, it's content.
     pub(super) fn parse_line(input: &str) -> IResult<&str, LineParseResult> {
         alt((
             parse_if_directive,
+            parse_elif_directive,
+            parse_else_directive,
             parse_endif_directive,
             map(rest, LineParseResult::Content), // If others fail, take the rest as content
         ))(input)
     }
+
+    // Best-effort location of the condition substring within a raw `#if`/`#elif` line, used to
+    // underline the offending text even when it failed to parse as a valid `Condition` at all
+    // (so the span is heuristic, not nom-verified, unlike `parse_if_directive`/`parse_elif_directive`).
+    pub(super) fn parse_condition_str(line: &str) -> (usize, usize) {
+        let trimmed_start = line.trim_start();
+        let prefix_len = line.len() - trimmed_start.len();
+        let after_keyword = trimmed_start
+            .strip_prefix("#if")
+            .or_else(|| trimmed_start.strip_prefix("#elif"))
+            .unwrap_or(trimmed_start);
+        let keyword_len = trimmed_start.len() - after_keyword.len();
+        let condition = after_keyword.trim();
+        let inner_offset = after_keyword.len() - after_keyword.trim_start().len();
+        let col_start = prefix_len + keyword_len + inner_offset;
+        (col_start, col_start + condition.len())
+    }
 } // end mod parser
 
 // --- Argument Parsing ---
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Process a source directory of templates into a destination directory
+    Process(ProcessArgs),
+    /// Run the fixture-driven testsuite against a directory of `.tmpl` files
+    Testsuite(testsuite::TestsuiteArgs),
+}
+
+#[derive(Args, Debug)]
+struct ProcessArgs {
     /// Source directory with templates
     #[arg(long = "from", default_value = ".", value_name = "SRC_DIR")]
     src_dir: PathBuf,
@@ -181,19 +363,54 @@ struct Args {
     /// Flags like `clj devshell` to include conditionals
     #[arg(required = true, num_args = 1..)]
     flags: Vec<String>,
+
+    /// Variable values for `${name}` substitution, e.g. `--var project_name=my-app`
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    vars: Vec<String>,
+
+    /// Fail instead of warning when a template references an unknown `${...}` variable
+    #[arg(long)]
+    strict: bool,
 }
 
 // --- Core Processing Logic ---
 
-/// Processes lines from a reader based on conditional blocks and flags.
+/// Tracks state for one open `#if`/`#elif`/`#else` chain.
+struct IfBlock {
+    /// Whether the scope enclosing this whole chain is active.
+    parent_active: bool,
+    /// Whether some branch in this chain has already matched.
+    branch_taken: bool,
+    /// Whether the *current* branch is active (its content should be emitted).
+    active: bool,
+    /// Whether an `#else` has already been seen in this chain.
+    else_seen: bool,
+    /// Line number of the `#if` that opened this chain, for `MismatchedIf` diagnostics.
+    open_line: usize,
+    /// Byte span of that `#if`'s condition within its line.
+    open_span: (usize, usize),
+}
+
+/// Processes lines from a reader based on conditional blocks and flags, substituting
+/// `${name}` variable placeholders into surviving content along the way.
 fn process_content(
     reader: impl BufRead,
     file_path: &Path, // For error context
     flags: &HashSet<String>,
     used_flags: &mut HashSet<String>,
+    vars: &HashMap<String, String>,
+    used_vars: &mut HashSet<String>,
+    strict: bool,
 ) -> Result<Vec<String>, ProcessorError> {
     let mut output = Vec::new();
-    let mut include_stack: VecDeque<bool> = VecDeque::from([true]);
+    let mut include_stack: VecDeque<IfBlock> = VecDeque::from([IfBlock {
+        parent_active: true,
+        branch_taken: true,
+        active: true,
+        else_seen: false,
+        open_line: 0,
+        open_span: (0, 0),
+    }]);
     let mut line_num = 0;
 
     for line_result in reader.lines() {
@@ -205,26 +422,91 @@ fn process_content(
 
         match parser::parse_line(&line) {
             Ok((_, parse_result)) => match parse_result {
-                parser::LineParseResult::If(condition) => {
-                    let current_block_active = *include_stack.back().unwrap_or(&false); // Should always have initial `true`
-                    let is_condition_met = condition.evaluate(flags, used_flags);
-                    include_stack.push_back(current_block_active && is_condition_met);
+                parser::LineParseResult::If(condition, span) => {
+                    let parent_active = include_stack.back().is_none_or(|b| b.active);
+                    // Only evaluate the condition if we're actually inside an active block,
+                    // so flags in an unentered branch are never marked used (`&&` short-circuits,
+                    // so `evaluate` is not called when the parent block is inactive).
+                    let matched = parent_active && condition.evaluate(flags, used_flags);
+                    include_stack.push_back(IfBlock {
+                        parent_active,
+                        branch_taken: matched,
+                        active: matched,
+                        else_seen: false,
+                        open_line: line_num,
+                        open_span: span,
+                    });
+                }
+                parser::LineParseResult::Elif(condition, _span) => {
+                    if include_stack.len() <= 1 {
+                        return Err(ProcessorError::MismatchedElse {
+                            line_num,
+                            path: file_path.to_path_buf(),
+                            reason: "#elif with no open #if".to_string(),
+                        });
+                    }
+                    let block = include_stack.back_mut().unwrap();
+                    if block.else_seen {
+                        return Err(ProcessorError::MismatchedElse {
+                            line_num,
+                            path: file_path.to_path_buf(),
+                            reason: "#elif found after #else".to_string(),
+                        });
+                    }
+                    if block.branch_taken {
+                        // A previous branch already matched; skip without evaluating.
+                        block.active = false;
+                    } else {
+                        let matched = block.parent_active && condition.evaluate(flags, used_flags);
+                        block.branch_taken = matched;
+                        block.active = matched;
+                    }
+                }
+                parser::LineParseResult::Else => {
+                    if include_stack.len() <= 1 {
+                        return Err(ProcessorError::MismatchedElse {
+                            line_num,
+                            path: file_path.to_path_buf(),
+                            reason: "#else with no open #if".to_string(),
+                        });
+                    }
+                    let block = include_stack.back_mut().unwrap();
+                    if block.else_seen {
+                        return Err(ProcessorError::MismatchedElse {
+                            line_num,
+                            path: file_path.to_path_buf(),
+                            reason: "duplicate #else".to_string(),
+                        });
+                    }
+                    block.active = block.parent_active && !block.branch_taken;
+                    block.branch_taken = true;
+                    block.else_seen = true;
                 }
-                parser::LineParseResult::Endif => {
+                parser::LineParseResult::Endif(col_start, col_end) => {
                     if include_stack.len() > 1 {
                         include_stack.pop_back();
                     } else {
                         return Err(ProcessorError::MismatchedEndif {
                             line_num,
+                            col_start,
+                            col_end,
                             path: file_path.to_path_buf(),
                         });
                     }
                 }
                 parser::LineParseResult::Content(content_str) => {
-                    if *include_stack.back().unwrap_or(&false) {
+                    if include_stack.back().is_some_and(|b| b.active) {
                         // Only push the relevant content part if nom didn't consume the whole line
                         // In our current parser setup, `Content` gets the *whole* original line.
-                        output.push(content_str.to_string());
+                        let substituted = substitute_vars(
+                            content_str,
+                            vars,
+                            used_vars,
+                            line_num,
+                            strict,
+                            file_path,
+                        )?;
+                        output.push(substituted);
                     }
                 }
             },
@@ -233,32 +515,34 @@ fn process_content(
                 // as Content covers everything else. Let's try to extract the condition part.
                 // A simpler approach: treat any parse failure on non-empty lines as potential error
                 if !line.trim().is_empty() {
-                    // Attempt to find the #if part to report it
-                    let relevant_slice: &str = line
-                        .trim_start()
-                        .strip_prefix("#if") // Returns Option<&str> containing the part *after* "#if"
-                        .unwrap_or(&line); // If no "#if", use the original line slice (&str via Deref<Target=str>)
-
-                    // Trim whitespace from the chosen slice and own it.
-                    let condition_part: String = relevant_slice.trim().to_owned();
-                    // let condition_part: &String = line.trim_start().strip_prefix("#if").map_or(&line, |s| &(s.trim().to_owned()));
+                    // Locate the condition substring (even though it failed to parse) so the
+                    // error can underline it.
+                    let (col_start, col_end) = parser::parse_condition_str(&line);
+                    let condition_part = line[col_start..col_end].to_string();
                     return Err(ProcessorError::ConditionParse {
-                        condition: condition_part.to_string(),
+                        condition: condition_part,
                         line_num,
+                        col_start,
+                        col_end,
                         path: file_path.to_path_buf(),
                         reason: format!("nom parser error: {:?}", e.code), // Provide nom error code
                     });
                 }
                 // Otherwise, likely an empty line or just whitespace, treat as content (if active)
-                else if *include_stack.back().unwrap_or(&false) {
-                    output.push(line);
+                else if include_stack.back().is_some_and(|b| b.active) {
+                    let substituted =
+                        substitute_vars(&line, vars, used_vars, line_num, strict, file_path)?;
+                    output.push(substituted);
                 }
             }
             Err(nom::Err::Incomplete(_)) => {
                 // Should not happen when reading complete lines
+                let (col_start, col_end) = parser::parse_condition_str(&line);
                 return Err(ProcessorError::ConditionParse {
                     condition: line.to_string(),
                     line_num,
+                    col_start,
+                    col_end,
                     path: file_path.to_path_buf(),
                     reason: "Incomplete line data for parser".to_string(),
                 });
@@ -267,7 +551,11 @@ fn process_content(
     }
 
     if include_stack.len() != 1 {
+        let block = include_stack.back().unwrap();
         Err(ProcessorError::MismatchedIf {
+            line_num: block.open_line,
+            col_start: block.open_span.0,
+            col_end: block.open_span.1,
             path: file_path.to_path_buf(),
         })
     } else {
@@ -275,20 +563,82 @@ fn process_content(
     }
 }
 
+/// Substitutes every `${name}` placeholder in `line` with its value from `vars`, recording
+/// each referenced name into `used_vars`. An unknown name is left in place (with a nearest-match
+/// suggestion printed to stderr) unless `strict` is set, in which case it's a hard error.
+fn substitute_vars(
+    line: &str,
+    vars: &HashMap<String, String>,
+    used_vars: &mut HashSet<String>,
+    line_num: usize,
+    strict: bool,
+    file_path: &Path,
+) -> Result<String, ProcessorError> {
+    let mut output = String::with_capacity(line.len());
+    let mut remaining = line;
+
+    while let Some(start) = remaining.find("${") {
+        output.push_str(&remaining[..start]);
+        let slice = &remaining[start..];
+
+        match parser::parse_var_token(slice) {
+            Ok((after, name)) => {
+                let token = &slice[..slice.len() - after.len()];
+                used_vars.insert(name.to_string());
+
+                match vars.get(name) {
+                    Some(value) => output.push_str(value),
+                    None if strict => {
+                        return Err(ProcessorError::UnknownVar {
+                            line_num,
+                            name: name.to_string(),
+                            path: file_path.to_path_buf(),
+                        });
+                    }
+                    None => {
+                        let candidates: Vec<&str> = vars.keys().map(String::as_str).collect();
+                        let mut msg =
+                            format!("Warning: unknown variable '{name}' at line {line_num}");
+                        if let Some(suggestion) = find_closest_match(name, &candidates) {
+                            msg.push_str(&format!(" -- did you mean '{suggestion}'?"));
+                        }
+                        eprintln!("{}", msg.yellow());
+                        output.push_str(token);
+                    }
+                }
+                remaining = after;
+            }
+            // "${" wasn't followed by a valid identifier and closing brace; keep it literally
+            // and resume scanning just past it.
+            Err(_) => {
+                output.push_str("${");
+                remaining = &slice[2..];
+            }
+        }
+    }
+    output.push_str(remaining);
+
+    Ok(output)
+}
+
 /// Processes a single template file.
 fn process_file(
     src_path: &Path,
     dest_path: &Path,
     flags: &HashSet<String>,
     used_flags: &mut HashSet<String>,
+    vars: &HashMap<String, String>,
+    used_vars: &mut HashSet<String>,
+    strict: bool,
 ) -> Result<&'static str> {
     // Returns status string
     let file = File::open(src_path)
         .with_context(|| format!("Failed to open source file: {}", src_path.display()))?;
     let reader = BufReader::new(file);
 
-    let processed_lines = process_content(reader, src_path, flags, used_flags)
-        .with_context(|| format!("Failed to process content of: {}", src_path.display()))?;
+    let processed_lines =
+        process_content(reader, src_path, flags, used_flags, vars, used_vars, strict)
+            .with_context(|| format!("Failed to process content of: {}", src_path.display()))?;
 
     if processed_lines.iter().all(|line| line.trim().is_empty()) {
         // Optionally remove the destination file if it exists and is now empty
@@ -346,16 +696,69 @@ fn scan_all_conditions(src_dir: &Path) -> Result<HashSet<String>> {
 
         for line_result in reader.lines() {
             let line = line_result.context("Failed to read line during scan")?;
-            // Use the parser to find #if directives and extract condition flags
-            if let Ok((_, parser::LineParseResult::If(condition))) = parser::parse_line(&line) {
-                seen_flags.extend(condition.mentioned_flags());
+            // Use the parser to find #if/#elif directives and extract condition flags
+            match parser::parse_line(&line) {
+                Ok((_, parser::LineParseResult::If(condition, _)))
+                | Ok((_, parser::LineParseResult::Elif(condition, _))) => {
+                    seen_flags.extend(condition.mentioned_flags());
+                }
+                // Ignore lines that don't parse as #if/#elif during scan
+                _ => {}
             }
-            // Ignore lines that don't parse as #if during scan
         }
     }
     Ok(seen_flags)
 }
 
+/// Scans all files in the source directory to find all unique `${name}` variables referenced.
+fn scan_all_vars(src_dir: &Path) -> Result<HashSet<String>> {
+    let mut seen_vars = HashSet::new();
+    for entry in walkdir::WalkDir::new(src_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+    {
+        let path = entry.path();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open file for scanning: {}", path.display()))?;
+        let reader = BufReader::new(file);
+
+        for line_result in reader.lines() {
+            let line = line_result.context("Failed to read line during scan")?;
+            let mut remaining = line.as_str();
+            while let Some(start) = remaining.find("${") {
+                match parser::parse_var_token(&remaining[start..]) {
+                    Ok((after, name)) => {
+                        seen_vars.insert(name.to_string());
+                        remaining = after;
+                    }
+                    Err(_) => remaining = &remaining[start + 2..],
+                }
+            }
+        }
+    }
+    Ok(seen_vars)
+}
+
+/// Extracts the would-be `and`/`or`/`not` keyword from a malformed parenthesized
+/// condition (e.g. `"(an foo bar)"`), so a `ConditionParse` error can suggest the
+/// likely intended keyword. `ConditionParse` only ever fires for structurally
+/// broken conditions — a merely misspelled flag name still parses fine as a bare
+/// `Condition::Flag` and just evaluates to `false` — so the keyword itself is the
+/// only part of the leftover text that's realistic to compare against a fixed,
+/// short candidate list.
+fn condition_keyword_suggestion(condition: &str) -> Option<&'static str> {
+    let after_paren = condition.strip_prefix('(')?;
+    let keyword_end = after_paren
+        .find(|c: char| c.is_whitespace() || c == ')')
+        .unwrap_or(after_paren.len());
+    let keyword = &after_paren[..keyword_end];
+    if keyword.is_empty() || matches!(keyword, "and" | "or" | "not") {
+        return None;
+    }
+    find_closest_match(keyword, &["and", "or", "not"])
+}
+
 // --- Helper for Unused Flag Suggestions ---
 fn find_closest_match<'a>(flag: &str, candidates: &[&'a str]) -> Option<&'a str> {
     candidates
@@ -371,12 +774,60 @@ fn find_closest_match<'a>(flag: &str, candidates: &[&'a str]) -> Option<&'a str>
         .copied()
 }
 
+// --- Span-based Diagnostics ---
+
+/// Renders a compiler-style annotated snippet: `source_line` followed by a `^^^` caret
+/// underline beneath the byte range `[col_start, col_end)`, and an optional
+/// `help: did you mean \`...\`?` line.
+fn render_span_error(
+    source_line: &str,
+    col_start: usize,
+    col_end: usize,
+    suggestion: Option<&str>,
+) -> String {
+    let col_end = col_end.max(col_start + 1);
+    let mut rendered = format!(
+        "{source_line}\n{}{}",
+        " ".repeat(col_start),
+        "^".repeat(col_end - col_start)
+    );
+    if let Some(suggestion) = suggestion {
+        rendered.push_str(&format!("\nhelp: did you mean `{suggestion}`?"));
+    }
+    rendered
+}
+
+/// Reads the 1-indexed `line_num`-th line of `path`, if it exists.
+fn read_source_line(path: &Path, line_num: usize) -> Option<String> {
+    let file = File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .nth(line_num.checked_sub(1)?)
+        .and_then(Result::ok)
+}
+
 // --- Main Execution ---
 fn main() -> Result<()> {
-    let args = Args::parse();
+    match Cli::parse().command {
+        Command::Process(args) => run_process(args),
+        Command::Testsuite(args) => testsuite::run(args),
+    }
+}
+
+/// Processes a source directory of templates into a destination directory.
+fn run_process(args: ProcessArgs) -> Result<()> {
     let flags: HashSet<String> = args.flags.into_iter().collect();
     let mut used_flags: HashSet<String> = HashSet::new();
 
+    let mut vars: HashMap<String, String> = HashMap::new();
+    for entry in &args.vars {
+        let (name, value) = entry.split_once('=').ok_or_else(|| {
+            anyhow!("Invalid --var '{entry}': expected KEY=VALUE")
+        })?;
+        vars.insert(name.to_string(), value.to_string());
+    }
+    let mut used_vars: HashSet<String> = HashSet::new();
+
     if !args.src_dir.is_dir() {
         return Err(anyhow!(
             "Source directory not found or not a directory: {}",
@@ -416,7 +867,15 @@ fn main() -> Result<()> {
             std::io::stdout().flush().ok();
         }
 
-        match process_file(src_path, &dest_path, &flags, &mut used_flags) {
+        match process_file(
+            src_path,
+            &dest_path,
+            &flags,
+            &mut used_flags,
+            &vars,
+            &mut used_vars,
+            args.strict,
+        ) {
             Ok("skipped") => {
                 files_skipped += 1;
                 if args.verbose {
@@ -448,6 +907,34 @@ fn main() -> Result<()> {
                     "{}",
                     format!("Error processing {}: {:?}", rel_path.display(), e).red()
                 );
+
+                // If the root cause is a ProcessorError with span info, render a
+                // compiler-style caret annotation underneath the offending source line.
+                if let Some(proc_err) =
+                    e.chain().find_map(|cause| cause.downcast_ref::<ProcessorError>())
+                {
+                    if let (Some(line_num), Some((col_start, col_end))) =
+                        (proc_err.line_num(), proc_err.span())
+                    {
+                        if let Some(source_line) = read_source_line(src_path, line_num) {
+                            let suggestion = match proc_err {
+                                ProcessorError::ConditionParse { condition, .. } => {
+                                    condition_keyword_suggestion(condition).map(str::to_string)
+                                }
+                                _ => None,
+                            };
+                            eprintln!(
+                                "{}",
+                                render_span_error(
+                                    &source_line,
+                                    col_start,
+                                    col_end,
+                                    suggestion.as_deref()
+                                )
+                            );
+                        }
+                    }
+                }
             }
         }
     }
@@ -542,6 +1029,44 @@ fn main() -> Result<()> {
         }
     }
 
+    // --- Unused Var Reporting ---
+    let unused_vars: Vec<&String> = vars.keys().filter(|v| !used_vars.contains(*v)).collect();
+
+    if !unused_vars.is_empty() {
+        println!("\n{}", "Unused variables:".yellow().bold());
+
+        match scan_all_vars(&args.src_dir) {
+            Ok(all_vars_set) => {
+                let all_vars_vec: Vec<&str> = all_vars_set.iter().map(String::as_str).collect();
+
+                for &unused_var in &unused_vars {
+                    let mut msg = format!(
+                        "  - Variable {} was provided but never referenced by a ${{...}} placeholder.",
+                        unused_var.red()
+                    );
+
+                    if !all_vars_set.contains(unused_var) {
+                        if let Some(suggestion) = find_closest_match(unused_var, &all_vars_vec) {
+                            msg.push_str(&format!(" Did you mean {}?", suggestion.green()));
+                        }
+                    }
+
+                    println!("{}", msg);
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    format!("\nWarning: Could not scan for all variables to provide suggestions: {:?}", e)
+                        .yellow()
+                );
+                for &unused_var in &unused_vars {
+                    println!("  - Unused variable: {}", unused_var.red());
+                }
+            }
+        }
+    }
+
     // Indicate error to shell if any file processing failed
     if files_error > 0 {
         std::process::exit(1);
@@ -0,0 +1,232 @@
+//! Fixture-driven regression tests: each `.tmpl` file under a directory declares its own
+//! flags, template body, and expected output (or expected error), so contributors can pin
+//! down a processor behavior by dropping in a new file instead of editing a test module.
+#[cfg(test)]
+mod testsuite_tests;
+
+use crate::*;
+use std::io::Cursor;
+
+/// Arguments for the `testsuite` subcommand.
+#[derive(Args, Debug)]
+pub(crate) struct TestsuiteArgs {
+    /// Directory containing `.tmpl` fixture files
+    #[arg(default_value = "testsuite", value_name = "FIXTURES_DIR")]
+    dir: PathBuf,
+}
+
+const SEPARATOR: &str = "===";
+const ERROR_MARKER: &str = "#!ERROR";
+
+/// A single parsed `.tmpl` fixture: the flags it declares, the template body to feed to
+/// `process_content`, and what that run is expected to produce.
+#[derive(Debug, PartialEq)]
+struct Fixture {
+    flags: HashSet<String>,
+    vars: HashMap<String, String>,
+    body: Vec<String>,
+    expected_error: Option<(usize, String)>,
+    expected_output: Vec<String>,
+}
+
+impl Fixture {
+    /// Parses fixture text of the form:
+    /// ```text
+    /// #flags: foo bar
+    /// #vars: project_name=my-app version=1.0
+    /// <template body, with an optional trailing `#!ERROR Variant` per offending line>
+    /// ===
+    /// <expected output, omitted entirely when the body carries a `#!ERROR` annotation>
+    /// ```
+    /// Both header lines are optional and, when present, must appear in that order.
+    fn parse(raw: &str) -> Result<Fixture, String> {
+        let mut lines = raw.lines().peekable();
+
+        let flags = match lines.peek() {
+            Some(line) if line.starts_with("#flags:") => lines
+                .next()
+                .unwrap()
+                .trim_start_matches("#flags:")
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        let mut vars = HashMap::new();
+        if let Some(line) = lines.peek() {
+            if let Some(rest) = line.strip_prefix("#vars:") {
+                lines.next();
+                for entry in rest.split_whitespace() {
+                    let (name, value) = entry
+                        .split_once('=')
+                        .ok_or_else(|| format!("malformed #vars entry '{entry}', expected KEY=VALUE"))?;
+                    vars.insert(name.to_string(), value.to_string());
+                }
+            }
+        }
+
+        let rest: Vec<&str> = lines.collect();
+        let separator_idx = rest.iter().position(|&line| line == SEPARATOR);
+        let body_lines = match separator_idx {
+            Some(idx) => &rest[..idx],
+            None => &rest[..],
+        };
+
+        let mut body = Vec::with_capacity(body_lines.len());
+        let mut expected_error = None;
+        for (i, line) in body_lines.iter().enumerate() {
+            match line.find(ERROR_MARKER) {
+                Some(marker_idx) => {
+                    let variant = line[marker_idx + ERROR_MARKER.len()..].trim();
+                    if variant.is_empty() {
+                        return Err(format!("line {}: empty {} annotation", i + 1, ERROR_MARKER));
+                    }
+                    expected_error = Some((i + 1, variant.to_string()));
+                    body.push(line[..marker_idx].trim_end().to_string());
+                }
+                None => body.push(line.to_string()),
+            }
+        }
+
+        let expected_output = match separator_idx {
+            Some(idx) => rest[idx + 1..].iter().map(|&l| l.to_string()).collect(),
+            None if expected_error.is_some() => Vec::new(),
+            None => return Err(format!("missing '{SEPARATOR}' separator before expected output")),
+        };
+
+        Ok(Fixture {
+            flags,
+            vars,
+            body,
+            expected_error,
+            expected_output,
+        })
+    }
+}
+
+/// The result of running one fixture, with a human-readable explanation on failure.
+enum Outcome {
+    Pass,
+    Fail(String),
+}
+
+/// Runs a single fixture's body through `process_content` and checks it against whichever
+/// of `expected_error`/`expected_output` the fixture declared.
+fn run_fixture(fixture: &Fixture) -> Outcome {
+    let mut used_flags = HashSet::new();
+    let mut used_vars = HashSet::new();
+    let reader = Cursor::new(fixture.body.join("\n"));
+    let result = process_content(
+        reader,
+        Path::new("<fixture>"),
+        &fixture.flags,
+        &mut used_flags,
+        &fixture.vars,
+        &mut used_vars,
+        false,
+    );
+
+    match (&fixture.expected_error, result) {
+        (Some((line_num, variant)), Err(err)) => {
+            let line_matches = err.line_num().is_none_or(|l| l == *line_num);
+            if err.variant_name() == variant && line_matches {
+                Outcome::Pass
+            } else {
+                Outcome::Fail(format!(
+                    "expected error {variant} at line {line_num}, got {} at {:?}: {err}",
+                    err.variant_name(),
+                    err.line_num()
+                ))
+            }
+        }
+        (Some((line_num, variant)), Ok(output)) => Outcome::Fail(format!(
+            "expected error {variant} at line {line_num}, but processing succeeded with:\n{}",
+            output.join("\n")
+        )),
+        (None, Err(err)) => Outcome::Fail(format!("unexpected error: {err}")),
+        (None, Ok(output)) => {
+            if output == fixture.expected_output {
+                Outcome::Pass
+            } else {
+                Outcome::Fail(unified_diff(&fixture.expected_output, &output))
+            }
+        }
+    }
+}
+
+/// Minimal unified-style diff between expected and actual output lines.
+fn unified_diff(expected: &[String], actual: &[String]) -> String {
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => out.push_str(&format!("  {e}\n")),
+            (Some(e), Some(a)) => {
+                out.push_str(&format!("- {e}\n"));
+                out.push_str(&format!("+ {a}\n"));
+            }
+            (Some(e), None) => out.push_str(&format!("- {e}\n")),
+            (None, Some(a)) => out.push_str(&format!("+ {a}\n")),
+            (None, None) => unreachable!(),
+        }
+    }
+    out
+}
+
+/// Runs every `.tmpl` fixture under `args.dir` and prints a pass/fail summary.
+pub(crate) fn run(args: TestsuiteArgs) -> Result<()> {
+    if !args.dir.is_dir() {
+        return Err(anyhow!(
+            "Testsuite directory not found or not a directory: {}",
+            args.dir.display()
+        ));
+    }
+
+    let mut passed = 0;
+    let mut failed = 0;
+
+    for entry in walkdir::WalkDir::new(&args.dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "tmpl"))
+    {
+        let path = entry.path();
+        let rel_path = path.strip_prefix(&args.dir).unwrap_or(path);
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read fixture: {}", path.display()))?;
+
+        let outcome = match Fixture::parse(&raw) {
+            Ok(fixture) => run_fixture(&fixture),
+            Err(e) => Outcome::Fail(format!("malformed fixture: {e}")),
+        };
+
+        match outcome {
+            Outcome::Pass => {
+                passed += 1;
+                println!("{} {}", "ok".green(), rel_path.display());
+            }
+            Outcome::Fail(diff) => {
+                failed += 1;
+                println!("{} {}", "FAIL".red().bold(), rel_path.display());
+                println!("{diff}");
+            }
+        }
+    }
+
+    println!(
+        "\nTestsuite summary: {} passed, {} failed",
+        passed.to_string().green(),
+        if failed == 0 {
+            "0".green()
+        } else {
+            failed.to_string().red()
+        }
+    );
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use super::*;
+
+#[test]
+fn test_parse_fixture_with_flags_and_output() {
+    let raw = "#flags: foo\nbefore\n#if foo\ncontent\n#endif\nafter\n===\nbefore\ncontent\nafter";
+    let fixture = Fixture::parse(raw).unwrap();
+    assert_eq!(fixture.flags, make_hashset(&["foo"]));
+    assert_eq!(
+        fixture.body,
+        vec!["before", "#if foo", "content", "#endif", "after"]
+    );
+    assert_eq!(fixture.expected_output, vec!["before", "content", "after"]);
+    assert!(fixture.expected_error.is_none());
+}
+
+#[test]
+fn test_parse_fixture_with_vars_header() {
+    let raw = "#flags:\n#vars: name=world\nHello, ${name}!\n===\nHello, world!";
+    let fixture = Fixture::parse(raw).unwrap();
+    assert_eq!(fixture.vars.get("name"), Some(&"world".to_string()));
+    assert!(matches!(run_fixture(&fixture), Outcome::Pass));
+}
+
+#[test]
+fn test_parse_fixture_without_flags_header() {
+    let raw = "line one\n===\nline one";
+    let fixture = Fixture::parse(raw).unwrap();
+    assert!(fixture.flags.is_empty());
+    assert_eq!(fixture.body, vec!["line one"]);
+}
+
+#[test]
+fn test_parse_fixture_with_error_annotation() {
+    let raw = "#flags:\ncontent\n#endif #!ERROR MismatchedEndif";
+    let fixture = Fixture::parse(raw).unwrap();
+    assert_eq!(fixture.body, vec!["content", "#endif"]);
+    assert_eq!(
+        fixture.expected_error,
+        Some((2, "MismatchedEndif".to_string()))
+    );
+    assert!(fixture.expected_output.is_empty());
+}
+
+#[test]
+fn test_parse_fixture_missing_separator_is_an_error() {
+    let raw = "#flags:\nno separator here";
+    assert!(Fixture::parse(raw).is_err());
+}
+
+#[test]
+fn test_parse_fixture_empty_error_annotation_is_an_error() {
+    let raw = "#flags:\ncontent #!ERROR ";
+    assert!(Fixture::parse(raw).is_err());
+}
+
+#[test]
+fn test_run_fixture_passes_on_matching_output() {
+    let fixture = Fixture::parse("#flags: foo\n#if foo\nyes\n#endif\n===\nyes").unwrap();
+    assert!(matches!(run_fixture(&fixture), Outcome::Pass));
+}
+
+#[test]
+fn test_run_fixture_fails_on_output_mismatch() {
+    let fixture = Fixture::parse("#flags: foo\n#if foo\nyes\n#endif\n===\nno").unwrap();
+    assert!(matches!(run_fixture(&fixture), Outcome::Fail(_)));
+}
+
+#[test]
+fn test_run_fixture_passes_on_matching_error() {
+    let fixture =
+        Fixture::parse("#flags:\ncontent\n#endif #!ERROR MismatchedEndif").unwrap();
+    assert!(matches!(run_fixture(&fixture), Outcome::Pass));
+}
+
+#[test]
+fn test_run_fixture_fails_on_wrong_error_variant() {
+    let fixture = Fixture::parse("#flags:\ncontent\n#endif #!ERROR MismatchedIf").unwrap();
+    assert!(matches!(run_fixture(&fixture), Outcome::Fail(_)));
+}
+
+fn make_hashset(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}